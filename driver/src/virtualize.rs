@@ -2,9 +2,11 @@ use {
     alloc::alloc::{alloc_zeroed, handle_alloc_error},
     core::{alloc::Layout, arch::global_asm},
     hypervisor::{
+        amd::svm::Svm,
         intel::{
-            capture::GuestRegisters, page::Page
+            capture::GuestRegisters, page::Page, vcpu::IntelVcpu
         },
+        vcpu::{Hypervisor, Vcpu},
         vmm::start_hypervisor
     },
     log::debug,
@@ -14,7 +16,77 @@ use {
     },
 };
 
+/// The vendor of the CPU the driver is virtualizing, as reported by `CPUID` leaf 0's vendor-ID
+/// string. Determines which backend (`hypervisor::intel` or `hypervisor::amd`) handles the rest
+/// of virtualization.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum CpuVendor {
+    /// `GenuineIntel`: use the VMX/EPT backend in `hypervisor::intel`.
+    Intel,
+    /// `AuthenticAMD`: use the SVM/NPT backend in `hypervisor::amd`.
+    Amd,
+    /// Any other (or unrecognized) vendor string; virtualization is not supported.
+    Unknown,
+}
+
+impl CpuVendor {
+    /// Detects the CPU vendor via `CPUID` leaf 0, whose EBX:EDX:ECX bytes spell out the 12-byte
+    /// vendor-ID string.
+    fn detect() -> Self {
+        let result = unsafe { core::arch::x86_64::__cpuid(0) };
+
+        let mut vendor_id = [0u8; 12];
+        vendor_id[0..4].copy_from_slice(&result.ebx.to_le_bytes());
+        vendor_id[4..8].copy_from_slice(&result.edx.to_le_bytes());
+        vendor_id[8..12].copy_from_slice(&result.ecx.to_le_bytes());
+
+        match &vendor_id {
+            b"GenuineIntel" => CpuVendor::Intel,
+            b"AuthenticAMD" => CpuVendor::Amd,
+            _ => CpuVendor::Unknown,
+        }
+    }
+}
+
 pub fn virtualize_system(regs: &GuestRegisters, system_table: &SystemTable<Boot>) {
+    let vendor = CpuVendor::detect();
+    debug!("Detected CPU vendor: {:?}", vendor);
+
+    // `vmm::start_hypervisor` below is the only backend actually wired up end to end so far; AMD
+    // hardware goes through `Svm::virtualize_core` (and any other vendor panics) instead of falling
+    // through to the Intel path, since the VMX setup `start_hypervisor` performs does not apply
+    // to it. `Svm` already implements `Hypervisor`/`Vcpu` (see `amd::svm`); `IntelVcpu` (see
+    // `intel::vcpu`) is the equivalent landing spot for the Intel backend once it migrates behind
+    // the same traits instead of calling `start_hypervisor` directly.
+    match vendor {
+        CpuVendor::Intel => {
+            // `IntelVcpu::virtualize_core` is still a `NotSupported` stub - its VMX setup
+            // (`VMXON`/VMCS activation) hasn't moved over from `start_hypervisor` yet - so fall
+            // through to the landing path below instead of panicking like the `Amd` arm does.
+            // Once that migration lands, this arm picks it up with no other change here.
+            if let Ok(mut vcpu) = IntelVcpu::virtualize_core(regs) {
+                loop {
+                    if let Err(err) = vcpu.run() {
+                        panic!("Intel VMX VM exit handling failed: {:?}", err);
+                    }
+                }
+            }
+        }
+        CpuVendor::Amd => {
+            let mut vcpu = match Svm::virtualize_core(regs) {
+                Ok(vcpu) => vcpu,
+                Err(err) => panic!("AMD-V backend is not implemented yet ({:?}); refusing to run the Intel VMX path on non-Intel hardware", err),
+            };
+
+            loop {
+                if let Err(err) = vcpu.run() {
+                    panic!("AMD-V VM exit handling failed: {:?}", err);
+                }
+            }
+        }
+        CpuVendor::Unknown => panic!("Unrecognized CPU vendor; virtualization is not supported"),
+    }
+
     let boot_service = system_table.boot_services();
 
     // Open the loaded image protocol to get the current image base and image size.
@@ -0,0 +1,14 @@
+//! AMD SVM/NPT backend.
+//!
+//! Implements the [`crate::vcpu::Hypervisor`]/[`crate::vcpu::Vcpu`] traits on top of `VMRUN`, the
+//! VMCB, and nested page tables, mirroring what the `intel` module does with VMX/EPT. Selected by
+//! `driver::virtualize_system` when CPUID reports an `AuthenticAMD` vendor string.
+//!
+//! This module is a scaffold: it holds the VMCB types and the `#VMEXIT` code decoding in
+//! `svm::Svm::run`, with no exit handlers (NPF, CPUID, VMMCALL, ...). `Svm::virtualize_core`,
+//! `Svm::install_hook`, and the `npt` functions return `HypervisorError::NotSupported`, so no
+//! guest runs and no stealth hook is installed through this backend.
+
+pub mod npt;
+pub mod svm;
+pub mod vmcb;
@@ -0,0 +1,39 @@
+//! Nested Page Tables (NPT): AMD-V's second-level address translation, serving the same role as
+//! Intel's EPT in `intel::ept` — translating guest-physical to system-physical addresses, and
+//! giving the hypervisor a place to install the stealth hooks that redirect execution of a hooked
+//! guest-physical page to a shadow copy.
+//!
+//! The table format itself reuses the standard 4-level x86-64 paging structures (unlike EPT,
+//! which defines its own PML4E/PDPTE/PDE/PTE bit layouts), so this module is expected to share
+//! much of its walking logic with `intel::addresses` once the entry permission bits are
+//! parameterized per backend.
+
+use crate::error::HypervisorError;
+
+/// A guest-physical to system-physical address mapping, analogous to `intel::ept::Ept`.
+pub struct NestedPageTable {
+    /// Physical address of the PML4 table, loaded into the VMCB's `N_CR3` field.
+    root_pa: u64,
+}
+
+impl NestedPageTable {
+    /// Constructor for an identity-mapped nested page table, the NPT counterpart to however
+    /// `intel::ept::Ept` bootstraps the primary EPT. Always returns
+    /// `HypervisorError::NotSupported`; no table is built.
+    pub fn new() -> Result<Self, HypervisorError> {
+        Err(HypervisorError::NotSupported)
+    }
+
+    /// The NPT counterpart of `intel::ept::Ept::swap_page`. Always returns
+    /// `HypervisorError::NotSupported`; no guest access is redirected from `guest_pa` to
+    /// `shadow_pa`.
+    pub fn swap_page(&mut self, guest_pa: u64, shadow_pa: u64) -> Result<(), HypervisorError> {
+        let _ = (guest_pa, shadow_pa);
+        Err(HypervisorError::NotSupported)
+    }
+
+    /// The physical address of the PML4 table, for loading into the VMCB's `N_CR3` field.
+    pub fn root_pa(&self) -> u64 {
+        self.root_pa
+    }
+}
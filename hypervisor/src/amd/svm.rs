@@ -0,0 +1,107 @@
+//! SVM vCPU: drives a single guest core via `VMRUN`, implementing the
+//! [`crate::vcpu::Hypervisor`]/[`crate::vcpu::Vcpu`] traits on top of the [`super::vmcb::Vmcb`].
+
+use {
+    crate::{
+        amd::vmcb::Vmcb,
+        error::HypervisorError,
+        intel::{addresses::PhysicalAddress, capture::GuestRegisters, ept::AccessType},
+        vcpu::{Hypervisor, Vcpu, VmExitReason},
+    },
+    core::arch::asm,
+    x86::controlregs::cr3,
+};
+
+/// A single AMD-V virtual CPU: its VMCB and the guest's register state. It holds no nested page
+/// tables of its own.
+pub struct Svm {
+    vmcb: Vmcb,
+    guest_registers: GuestRegisters,
+}
+
+impl Hypervisor for Svm {
+    type Vcpu = Svm;
+
+    fn virtualize_core(initial_registers: &GuestRegisters) -> Result<Self::Vcpu, HypervisorError> {
+        // Enabling SVM requires setting `EFER.SVME`, allocating the host-save area MSR target,
+        // and allocating a zeroed VMCB before the first `VMRUN`. The nested page tables backing
+        // `N_CR3` are built the same way `intel::ept` builds the primary EPT.
+        let _ = initial_registers;
+        Err(HypervisorError::NotSupported)
+    }
+}
+
+impl Vcpu for Svm {
+    fn run(&mut self) -> Result<VmExitReason, HypervisorError> {
+        let vmcb_pa = host_physical_address(&self.vmcb as *const Vmcb as u64)?;
+
+        // SAFETY: `vmrun` transfers control to the guest described by the VMCB at physical
+        // address `vmcb_pa` and returns control to the instruction after the `vmrun` on
+        // `#VMEXIT`; the VMCB's physical (not virtual) address must be loaded beforehand.
+        unsafe {
+            asm!("vmrun", in("rax") vmcb_pa, options(nostack));
+        }
+
+        // These are SVM's own `#VMEXIT` codes, not VMX's basic exit reasons - `VMMCALL` is 0x81
+        // here, unlike VMX's `VMCALL` at 18 (0x12).
+        match self.vmcb.control_area.exit_code {
+            0x81 => Ok(VmExitReason::Vmcall),
+            0x72 => Ok(VmExitReason::Cpuid),
+            0x400 => Ok(VmExitReason::NestedPageFault),
+            other => Ok(VmExitReason::Other(other as u32)),
+        }
+    }
+
+    fn guest_registers(&self) -> &GuestRegisters {
+        &self.guest_registers
+    }
+
+    fn guest_registers_mut(&mut self) -> &mut GuestRegisters {
+        &mut self.guest_registers
+    }
+
+    fn install_hook(&mut self, guest_pa: u64, shadow_pa: u64, _access_type: AccessType) -> Result<(), HypervisorError> {
+        // The counterpart of `intel::ept::Ept::swap_page` for the nested page tables rooted at
+        // `self.vmcb.control_area.ncr3`; no table is rewritten and the call always fails.
+        let _ = (guest_pa, shadow_pa);
+        Err(HypervisorError::NotSupported)
+    }
+
+    fn inject_event(&mut self, vector: u8, error_code: Option<u32>) {
+        const VALID: u64 = 1 << 31;
+        const DELIVER_ERROR_CODE: u64 = 1 << 11;
+        const TYPE_SHIFT: u64 = 8;
+
+        // SVM's EVENTINJ TYPE field (bits 10:8) mirrors VMX's interruption-type subfield: 0 for an
+        // external interrupt, 2 for NMI, 3 for a hardware exception. Leaving it at its default of
+        // 0 would deliver every injected event, `#GP` included, as an external interrupt instead
+        // of the exception/NMI type the guest's IDT dispatch expects.
+        const TYPE_EXTERNAL_INTERRUPT: u64 = 0;
+        const TYPE_NMI: u64 = 2;
+        const TYPE_EXCEPTION: u64 = 3;
+
+        let interruption_type = match vector {
+            2 => TYPE_NMI,
+            0..=31 => TYPE_EXCEPTION,
+            _ => TYPE_EXTERNAL_INTERRUPT,
+        };
+
+        let mut event_injection = vector as u64 | (interruption_type << TYPE_SHIFT) | VALID;
+
+        if let Some(error_code) = error_code {
+            event_injection |= DELIVER_ERROR_CODE | ((error_code as u64) << 32);
+        }
+
+        self.vmcb.control_area.event_injection = event_injection;
+    }
+}
+
+/// Translates a host virtual address to its physical address by walking the host's own page
+/// tables rooted at the live `CR3`, the same walk `intel::addresses` does for guest addresses.
+/// `VMRUN` requires the VMCB's physical address in RAX, not its virtual address.
+fn host_physical_address(va: u64) -> Result<u64, HypervisorError> {
+    // SAFETY: reads CR3 without modifying any state.
+    let host_cr3 = unsafe { cr3() };
+
+    PhysicalAddress::pa_from_va(host_cr3, va)
+}
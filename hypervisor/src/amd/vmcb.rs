@@ -0,0 +1,56 @@
+//! The Virtual Machine Control Block (VMCB): AMD-V's counterpart to Intel VMX's VMCS. A single
+//! 4 KiB structure holds both the control area (intercept bitmaps, ASID, nested-paging CR3, event
+//! injection) and the guest state area (segment registers, control registers, RIP/RSP/RFLAGS),
+//! unlike VMX which keeps per-field encodings behind `VMREAD`/`VMWRITE`.
+
+use crate::intel::page::Page;
+
+/// The control area occupying the first 0x400 bytes of the VMCB (AMD64 APM Vol. 2, Table B-1).
+/// Field offsets are defined by the architecture and must not be reordered.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct VmcbControlArea {
+    /// Intercept vectors and control bits (read/write/exception intercepts, `VMRUN` intercept,
+    /// nested paging enable, ...).
+    pub intercepts: [u32; 16],
+
+    /// Physical address of the nested page table root (`N_CR3`), analogous to EPTP on Intel.
+    pub ncr3: u64,
+
+    /// Address-space identifier distinguishing this guest's TLB entries from the host's and other
+    /// guests'.
+    pub guest_asid: u32,
+
+    /// Event injection requested for the next `VMRUN`, analogous to the VM-entry
+    /// interruption-information field on Intel.
+    pub event_injection: u64,
+
+    /// Exit code and exit-info fields populated by hardware on `#VMEXIT`.
+    pub exit_code: u64,
+    pub exit_info_1: u64,
+    pub exit_info_2: u64,
+    pub exit_int_info: u64,
+}
+
+/// The guest state area occupying the VMCB's second 0x400 bytes, holding the architectural
+/// register state hardware saves/restores on `VMRUN`/`#VMEXIT`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct VmcbStateSaveArea {
+    pub cr0: u64,
+    pub cr3: u64,
+    pub cr4: u64,
+    pub rflags: u64,
+    pub rip: u64,
+    pub rsp: u64,
+    pub efer: u64,
+}
+
+/// A full VMCB: one control area, one state-save area, padded out to the architectural 4 KiB
+/// page size hardware expects it to occupy.
+#[repr(C, align(4096))]
+pub struct Vmcb {
+    pub control_area: VmcbControlArea,
+    pub state_save_area: VmcbStateSaveArea,
+    _reserved: Page,
+}
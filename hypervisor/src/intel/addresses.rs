@@ -0,0 +1,258 @@
+//! Translation between guest-virtual and guest-physical addresses.
+//!
+//! The hypervisor frequently needs to dereference guest-virtual pointers (the faulting
+//! instruction on a VMCALL, a pointer a client passes via a hypercall, ...) without relying on
+//! the guest's own page tables being walked for it by hardware. This module provides that
+//! translation via a software walk of the guest's paging structures, honoring the guest's
+//! current CR3 rather than assuming any particular mapping.
+
+use crate::error::HypervisorError;
+
+/// The guest-physical page size a translation resolved to, as determined by the PS bit at the
+/// level the walk terminated on.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PageSize {
+    /// A standard 4 KiB page, resolved at the PT level.
+    Base,
+
+    /// A 2 MiB large page, resolved at the PD level (PS bit set).
+    Large,
+
+    /// A 1 GiB huge page, resolved at the PDPT level (PS bit set).
+    Huge,
+}
+
+impl PageSize {
+    /// The size, in bytes, of a page of this size.
+    pub fn size_in_bytes(self) -> u64 {
+        match self {
+            PageSize::Base => 0x1000,
+            PageSize::Large => 0x200000,
+            PageSize::Huge => 0x40000000,
+        }
+    }
+}
+
+/// A page-table entry's index at each of the four paging levels, extracted from a guest-virtual
+/// address. Bits 47:39 select the PML4E, 38:30 the PDPTE, 29:21 the PDE, and 20:12 the PTE.
+struct VaIndices {
+    pml4: usize,
+    pdpt: usize,
+    pd: usize,
+    pt: usize,
+    offset: u64,
+}
+
+impl VaIndices {
+    fn from_va(va: u64) -> Self {
+        Self {
+            pml4: ((va >> 39) & 0x1ff) as usize,
+            pdpt: ((va >> 30) & 0x1ff) as usize,
+            pd: ((va >> 21) & 0x1ff) as usize,
+            pt: ((va >> 12) & 0x1ff) as usize,
+            offset: va & 0xfff,
+        }
+    }
+}
+
+const PRESENT: u64 = 1 << 0;
+const WRITABLE: u64 = 1 << 1;
+const PAGE_SIZE_BIT: u64 = 1 << 7;
+const NX_BIT: u64 = 1 << 63;
+const PHYS_ADDR_MASK: u64 = 0x000f_ffff_ffff_f000;
+
+/// Namespace for guest-virtual to guest-physical address translation.
+pub struct PhysicalAddress;
+
+impl PhysicalAddress {
+    /// Translates a guest-virtual address to a guest-physical address by walking the paging
+    /// structures rooted at `guest_cr3`.
+    ///
+    /// This is a thin convenience wrapper over [`PhysicalAddress::translate_guest_va`] for callers
+    /// that only need the address (for example, the guest RIP on a VMCALL exit) and not the page
+    /// size the walk terminated on.
+    ///
+    /// # Arguments
+    ///
+    /// * `guest_cr3` - The guest CR3 (physical address of the PML4 table) to walk.
+    /// * `guest_va` - The guest-virtual address to translate.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(u64)` - The resolved guest-physical address.
+    /// * `Err(HypervisorError)` - If any level of the walk is not present.
+    pub fn pa_from_va(guest_cr3: u64, guest_va: u64) -> Result<u64, HypervisorError> {
+        Self::translate_guest_va(guest_cr3, guest_va).map(|(pa, _)| pa)
+    }
+
+    /// Walks the guest's paging structures, starting from `guest_cr3`, to resolve `guest_va` to a
+    /// guest-physical address, honoring the present bit and the PS (large/huge page) bit at every
+    /// level. The NX bit is not consulted by the walk; [`PhysicalAddress::is_no_execute`] tests it
+    /// on a single entry.
+    ///
+    /// Unlike assuming a trivially mapped (identity or fixed-offset) address space, this walks the
+    /// actual guest-controlled PML4 → PDPT → PD → PT chain, so it resolves correctly regardless of
+    /// ASLR, large-page mappings, or whether `guest_cr3` is the currently active CR3.
+    ///
+    /// # Arguments
+    ///
+    /// * `guest_cr3` - The guest CR3 (physical address of the PML4 table) to walk.
+    /// * `guest_va` - The guest-virtual address to translate.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok((u64, PageSize))` - The resolved guest-physical address and the page size the walk
+    ///   terminated on.
+    /// * `Err(HypervisorError::GuestPageNotPresent)` - If a table or page is not present at some
+    ///   level of the walk, so the caller can inject a page fault rather than act on a bogus PA.
+    pub fn translate_guest_va(guest_cr3: u64, guest_va: u64) -> Result<(u64, PageSize), HypervisorError> {
+        Self::translate_guest_va_inner(guest_cr3, guest_va).map(|(pa, page_size, _)| (pa, page_size))
+    }
+
+    /// Same walk as [`PhysicalAddress::translate_guest_va`], additionally tracking whether every
+    /// level of the walk had its R/W bit set: on x86 paging, a page is only actually writable if
+    /// the PML4E, PDPTE, PDE, and (for a 4 KiB page) PTE all grant write access, since hardware
+    /// ANDs the permission together across levels.
+    fn translate_guest_va_inner(guest_cr3: u64, guest_va: u64) -> Result<(u64, PageSize, bool), HypervisorError> {
+        let indices = VaIndices::from_va(guest_va);
+
+        let pml4_pa = guest_cr3 & PHYS_ADDR_MASK;
+        let pml4e = Self::read_entry(pml4_pa, indices.pml4)?;
+        Self::ensure_present(pml4e)?;
+
+        let pdpt_pa = pml4e & PHYS_ADDR_MASK;
+        let pdpte = Self::read_entry(pdpt_pa, indices.pdpt)?;
+        Self::ensure_present(pdpte)?;
+
+        let mut writable = pml4e & WRITABLE != 0 && pdpte & WRITABLE != 0;
+
+        if pdpte & PAGE_SIZE_BIT != 0 {
+            // 1 GiB huge page: bits 29:0 of the VA are the offset within the page.
+            let page_base = pdpte & 0x000f_ffff_c000_0000;
+            let offset = guest_va & 0x3fff_ffff;
+            return Ok((page_base | offset, PageSize::Huge, writable));
+        }
+
+        let pd_pa = pdpte & PHYS_ADDR_MASK;
+        let pde = Self::read_entry(pd_pa, indices.pd)?;
+        Self::ensure_present(pde)?;
+
+        writable &= pde & WRITABLE != 0;
+
+        if pde & PAGE_SIZE_BIT != 0 {
+            // 2 MiB large page: bits 20:0 of the VA are the offset within the page.
+            let page_base = pde & 0x000f_ffff_ffe0_0000;
+            let offset = guest_va & 0x1f_ffff;
+            return Ok((page_base | offset, PageSize::Large, writable));
+        }
+
+        let pt_pa = pde & PHYS_ADDR_MASK;
+        let pte = Self::read_entry(pt_pa, indices.pt)?;
+        Self::ensure_present(pte)?;
+
+        writable &= pte & WRITABLE != 0;
+
+        let page_base = pte & PHYS_ADDR_MASK;
+        Ok((page_base | indices.offset, PageSize::Base, writable))
+    }
+
+    /// Validates that a guest-virtual range of `len` bytes starting at `guest_va` lies entirely
+    /// within present, writable guest pages reachable from `guest_cr3`, without assuming the
+    /// range fits in a single page.
+    ///
+    /// Use this for buffers the hypervisor writes *through* (a multicall entry's `status` field,
+    /// a snapshot-dump destination buffer); use [`PhysicalAddress::validate_guest_range_readable`]
+    /// for buffers that are only read, such as a snapshot blob handed back for restore, which is
+    /// legitimately read-only guest input.
+    ///
+    /// # Arguments
+    ///
+    /// * `guest_cr3` - The guest CR3 (physical address of the PML4 table) to walk.
+    /// * `guest_va` - The first guest-virtual address of the range.
+    /// * `len` - The length of the range, in bytes.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - Every page backing the range is present and writable.
+    /// * `Err(HypervisorError::GuestPageNotPresent)` - Some page in the range is not present.
+    /// * `Err(HypervisorError::GuestPageNotWritable)` - Some page in the range is present but
+    ///   read-only, so a caller that writes through it would otherwise clobber a guest read-only
+    ///   page through the host identity map.
+    pub fn validate_guest_range(guest_cr3: u64, guest_va: u64, len: u64) -> Result<(), HypervisorError> {
+        Self::walk_guest_range(guest_cr3, guest_va, len, true)
+    }
+
+    /// Validates that a guest-virtual range of `len` bytes starting at `guest_va` lies entirely
+    /// within present guest pages reachable from `guest_cr3`, without requiring them to be
+    /// writable. Use this for buffers the hypervisor only reads from; see
+    /// [`PhysicalAddress::validate_guest_range`] for the writable variant.
+    ///
+    /// # Arguments
+    ///
+    /// * `guest_cr3` - The guest CR3 (physical address of the PML4 table) to walk.
+    /// * `guest_va` - The first guest-virtual address of the range.
+    /// * `len` - The length of the range, in bytes.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - Every page backing the range is present.
+    /// * `Err(HypervisorError::GuestPageNotPresent)` - Some page in the range is not present.
+    pub fn validate_guest_range_readable(guest_cr3: u64, guest_va: u64, len: u64) -> Result<(), HypervisorError> {
+        Self::walk_guest_range(guest_cr3, guest_va, len, false)
+    }
+
+    /// Shared walk backing [`PhysicalAddress::validate_guest_range`] and
+    /// [`PhysicalAddress::validate_guest_range_readable`]; `require_writable` selects whether the
+    /// R/W bit is checked at every level.
+    fn walk_guest_range(guest_cr3: u64, guest_va: u64, len: u64, require_writable: bool) -> Result<(), HypervisorError> {
+        if len == 0 {
+            return Ok(());
+        }
+
+        let last_byte = guest_va.checked_add(len - 1).ok_or(HypervisorError::GuestPageNotPresent)?;
+
+        let mut va = guest_va & !0xfff;
+        while va <= last_byte {
+            let (_, page_size, writable) = Self::translate_guest_va_inner(guest_cr3, va)?;
+
+            if require_writable && !writable {
+                return Err(HypervisorError::GuestPageNotWritable);
+            }
+
+            // Advance to the start of the next page, not by a page size from an unaligned `va`,
+            // so a large page followed by smaller ones doesn't skip any of them.
+            let size = page_size.size_in_bytes();
+            va = (va & !(size - 1)) + size;
+        }
+
+        Ok(())
+    }
+
+    /// Reads the 64-bit paging-structure entry at `index` within the table located at guest
+    /// physical address `table_pa`.
+    ///
+    /// # Safety
+    ///
+    /// This relies on the host identity-mapping all guest physical memory, as is the case
+    /// elsewhere in this crate (see `ClientData::from_ptr`), so `table_pa` can be dereferenced
+    /// directly once masked down to a table-aligned address.
+    fn read_entry(table_pa: u64, index: usize) -> Result<u64, HypervisorError> {
+        let entry_pa = table_pa + (index as u64) * 8;
+        Ok(unsafe { core::ptr::read_volatile(entry_pa as *const u64) })
+    }
+
+    /// Returns an error if a paging-structure entry's present bit is clear.
+    fn ensure_present(entry: u64) -> Result<(), HypervisorError> {
+        if entry & PRESENT == 0 {
+            Err(HypervisorError::GuestPageNotPresent)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns whether a paging-structure entry has the NX (no-execute) bit set, for callers that
+    /// need to reject installing an execute hook on a non-executable page.
+    pub fn is_no_execute(entry: u64) -> bool {
+        entry & NX_BIT != 0
+    }
+}
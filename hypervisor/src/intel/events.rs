@@ -0,0 +1,285 @@
+//! Injection of events (exceptons, NMIs, interrupts) into the guest, including a small queue that
+//! re-injects an event that was itself interrupted by the VM exit that is currently being
+//! handled.
+//!
+//! Naively injecting an event fire-and-forget on every VM exit that wants one (as
+//! `vmexit::vmcall::handle_vmcall` used to do for `#GP`) ignores two things hardware tells us
+//! about: a VM-entry can fail to deliver the event it was asked to inject, and an exit can happen
+//! *while* the processor was in the middle of delivering a previous event, reported via the
+//! IDT-vectoring-information field. Dropping either case risks the event never being delivered at
+//! all, or two events racing into the same VM-entry. This module centralizes both cases so only
+//! one event is ever in flight for injection at a time.
+
+use {crate::error::HypervisorError, alloc::collections::VecDeque};
+
+/// The class of event being injected, mirroring the `Interruption-type` subfield of the
+/// VM-entry/IDT-vectoring interruption-information fields (Intel SDM Vol. 3C, 24.8.3 / 24.9.4).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub enum InterruptionType {
+    /// An external (maskable) interrupt.
+    ExternalInterrupt = 0,
+    /// A non-maskable interrupt.
+    Nmi = 2,
+    /// A hardware exception (fault or trap), possibly carrying an error code.
+    HardwareException = 3,
+    /// A software interrupt raised by `INT n`.
+    SoftwareInterrupt = 4,
+    /// A privileged software exception (`INT1`/`ICEBP`).
+    PrivilegedSoftwareException = 5,
+    /// A software exception raised by `INT3` or `INTO`.
+    SoftwareException = 6,
+}
+
+/// A single event (exception, NMI, or interrupt) awaiting injection into the guest.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PendingEvent {
+    /// The interrupt/exception vector.
+    pub vector: u8,
+
+    /// The class of event this vector represents.
+    pub interruption_type: InterruptionType,
+
+    /// The error code to push for events that carry one (e.g. `#GP`, `#PF`).
+    pub error_code: Option<u32>,
+
+    /// The length, in bytes, of the instruction that caused the event, required for software
+    /// interrupts/exceptions so the processor can correctly report `RIP` on a nested exit.
+    pub instruction_length: Option<u8>,
+}
+
+impl PendingEvent {
+    /// Builds the VM-entry/IDT-vectoring interruption-information field value for this event,
+    /// with the valid bit (bit 31) and the deliver-error-code bit (bit 11) set as appropriate.
+    fn to_interruption_info(self) -> u32 {
+        const VALID: u32 = 1 << 31;
+        const DELIVER_ERROR_CODE: u32 = 1 << 11;
+
+        let mut info = self.vector as u32 | ((self.interruption_type as u32) << 8) | VALID;
+
+        if self.error_code.is_some() {
+            info |= DELIVER_ERROR_CODE;
+        }
+
+        info
+    }
+
+    /// Reconstructs a `PendingEvent` from a VM-entry/IDT-vectoring interruption-information field
+    /// and, if the field indicates an error code was delivered, the associated error-code field.
+    ///
+    /// # Returns
+    ///
+    /// * `Some(PendingEvent)` if the valid bit (bit 31) is set.
+    /// * `None` if no event was pending (the field is not valid).
+    fn from_fields(interruption_info: u32, error_code: u32, instruction_length: Option<u8>) -> Option<Self> {
+        const VALID: u32 = 1 << 31;
+        const DELIVER_ERROR_CODE: u32 = 1 << 11;
+        const TYPE_SHIFT: u32 = 8;
+        const TYPE_MASK: u32 = 0b111;
+
+        if interruption_info & VALID == 0 {
+            return None;
+        }
+
+        let interruption_type = match (interruption_info >> TYPE_SHIFT) & TYPE_MASK {
+            0 => InterruptionType::ExternalInterrupt,
+            2 => InterruptionType::Nmi,
+            3 => InterruptionType::HardwareException,
+            4 => InterruptionType::SoftwareInterrupt,
+            5 => InterruptionType::PrivilegedSoftwareException,
+            _ => InterruptionType::SoftwareException,
+        };
+
+        Some(Self {
+            vector: (interruption_info & 0xff) as u8,
+            interruption_type,
+            error_code: (interruption_info & DELIVER_ERROR_CODE != 0).then_some(error_code),
+            instruction_length,
+        })
+    }
+}
+
+/// A small FIFO queue of events awaiting injection into the guest.
+///
+/// Lives on `Vm` so it survives across VM exits: an event that was being delivered when an exit
+/// occurred, or one the hypervisor wants to inject but couldn't this entry (e.g. the guest has
+/// interrupts masked), is pushed here and drained one event per VM-entry by
+/// [`EventInjection::inject_pending_event`].
+#[derive(Debug, Default)]
+pub struct PendingEventQueue {
+    events: VecDeque<PendingEvent>,
+}
+
+impl PendingEventQueue {
+    /// Creates an empty pending-event queue.
+    pub fn new() -> Self {
+        Self { events: VecDeque::new() }
+    }
+
+    /// Queues an event for injection on a future VM-entry.
+    pub fn push(&mut self, event: PendingEvent) {
+        self.events.push_back(event);
+    }
+
+    /// Removes and returns the next event to inject, if any.
+    pub fn pop(&mut self) -> Option<PendingEvent> {
+        self.events.pop_front()
+    }
+
+    /// Returns the next event to inject without removing it, if any.
+    pub fn front(&self) -> Option<&PendingEvent> {
+        self.events.front()
+    }
+
+    /// Returns `true` if no events are queued.
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+/// Namespace for injecting events into the guest via the VM-entry interruption-information VMCS
+/// field.
+pub struct EventInjection;
+
+impl EventInjection {
+    /// Queues a General Protection Fault (`#GP`) with the given error code for delivery, via
+    /// [`EventInjection::queue_and_inject`], so it is serialized against any event already in
+    /// flight instead of racing it into the VMCS injection fields.
+    ///
+    /// # Arguments
+    ///
+    /// * `queue` - The VM's pending-event queue.
+    /// * `error_code` - The error code to push onto the guest stack for the fault.
+    pub fn vmentry_inject_gp(queue: &mut PendingEventQueue, error_code: u32) {
+        Self::queue_and_inject(
+            queue,
+            PendingEvent {
+                vector: 13,
+                interruption_type: InterruptionType::HardwareException,
+                error_code: Some(error_code),
+                instruction_length: None,
+            },
+        );
+    }
+
+    /// Queues an NMI for delivery, via [`EventInjection::queue_and_inject`].
+    ///
+    /// # Arguments
+    ///
+    /// * `queue` - The VM's pending-event queue.
+    pub fn vmentry_inject_nmi(queue: &mut PendingEventQueue) {
+        Self::queue_and_inject(
+            queue,
+            PendingEvent {
+                vector: 2,
+                interruption_type: InterruptionType::Nmi,
+                error_code: None,
+                instruction_length: None,
+            },
+        );
+    }
+
+    /// Writes an event directly into the VM-entry interruption-information, error-code, and
+    /// instruction-length VMCS fields so it is delivered on the very next VM-entry.
+    ///
+    /// Callers that need re-injection semantics (only one event in flight, events that arrive
+    /// while another is pending get queued rather than racing it) should go through
+    /// [`EventInjection::queue_and_inject`] instead of calling this directly.
+    fn inject(event: PendingEvent) {
+        unsafe {
+            crate::intel::vmcs::vmwrite(crate::intel::vmcs::VmcsField::VmEntryInterruptionInfoField, event.to_interruption_info() as u64);
+
+            if let Some(error_code) = event.error_code {
+                crate::intel::vmcs::vmwrite(crate::intel::vmcs::VmcsField::VmEntryExceptionErrorCode, error_code as u64);
+            }
+
+            if let Some(instruction_length) = event.instruction_length {
+                crate::intel::vmcs::vmwrite(crate::intel::vmcs::VmcsField::VmEntryInstructionLen, instruction_length as u64);
+            }
+        }
+    }
+
+    /// Queues `event` and, if no other event is already queued, immediately injects it; otherwise
+    /// it waits in the queue behind the earlier events until
+    /// [`EventInjection::inject_pending_event`] drains it on a later VM-entry.
+    ///
+    /// # Arguments
+    ///
+    /// * `queue` - The VM's pending-event queue.
+    /// * `event` - The event to deliver.
+    pub fn queue_and_inject(queue: &mut PendingEventQueue, event: PendingEvent) {
+        let others_queued = !queue.is_empty();
+        queue.push(event);
+
+        if !others_queued {
+            Self::inject_pending_event(queue);
+        }
+    }
+
+    /// Reads the IDT-vectoring-information field left behind by the VM exit that just occurred
+    /// and, if it indicates an event was being delivered to the guest when the exit happened,
+    /// re-queues that event so it is not lost.
+    ///
+    /// This must be called on every VM exit, before the exit is otherwise handled, so that an
+    /// event interrupted mid-delivery (the textbook cause of injected NMIs/exceptions looping
+    /// when dropped instead of re-queued) is captured exactly once.
+    ///
+    /// # Arguments
+    ///
+    /// * `queue` - The VM's pending-event queue to re-queue the interrupted event into.
+    pub fn requeue_interrupted_event(queue: &mut PendingEventQueue) {
+        let idt_vectoring_info = unsafe { crate::intel::vmcs::vmread(crate::intel::vmcs::VmcsField::IdtVectoringInfoField) as u32 };
+        let idt_vectoring_error_code = unsafe { crate::intel::vmcs::vmread(crate::intel::vmcs::VmcsField::IdtVectoringErrorCode) as u32 };
+        let instruction_length = unsafe { crate::intel::vmcs::vmread(crate::intel::vmcs::VmcsField::VmExitInstructionLen) as u8 };
+
+        if let Some(event) = PendingEvent::from_fields(idt_vectoring_info, idt_vectoring_error_code, Some(instruction_length)) {
+            queue.push(event);
+        }
+    }
+
+    /// Injects at most one pending event on this VM-entry, leaving the rest of the queue for
+    /// subsequent entries.
+    ///
+    /// Does nothing if the queue is empty. This function does not itself check whether a
+    /// single-step (MTF) cycle is in progress or whether the guest can receive the event;
+    /// `vmexit::arm_next_event` checks both before calling it.
+    ///
+    /// # Arguments
+    ///
+    /// * `queue` - The VM's pending-event queue.
+    ///
+    /// # Returns
+    ///
+    /// * `true` if an event was injected this entry.
+    /// * `false` if the queue was empty.
+    pub fn inject_pending_event(queue: &mut PendingEventQueue) -> bool {
+        let Some(event) = queue.pop() else {
+            return false;
+        };
+
+        Self::inject(event);
+
+        true
+    }
+}
+
+/// Enables or disables NMI-window exiting, so a pending NMI that could not be injected
+/// immediately (because the guest was already blocking NMIs) causes an exit as soon as the guest
+/// is ready for one rather than being injected blindly.
+///
+/// # Arguments
+///
+/// * `enable` - Whether NMI-window exiting should be active.
+pub fn set_nmi_window_exiting(enable: bool) -> Result<(), HypervisorError> {
+    crate::intel::controls::set_nmi_window_exiting(enable)
+}
+
+/// Enables or disables interrupt-window exiting, the external-interrupt analogue of
+/// [`set_nmi_window_exiting`].
+///
+/// # Arguments
+///
+/// * `enable` - Whether interrupt-window exiting should be active.
+pub fn set_interrupt_window_exiting(enable: bool) -> Result<(), HypervisorError> {
+    crate::intel::controls::set_interrupt_window_exiting(enable)
+}
@@ -1,3 +1,4 @@
+pub mod addresses;
 pub mod capture;
 pub mod controls;
 pub mod descriptor;
@@ -10,8 +11,10 @@ pub mod page;
 pub mod paging;
 pub mod segmentation;
 pub mod shared;
+pub mod snapshot;
 pub mod state;
 pub mod support;
+pub mod vcpu;
 pub mod vm;
 pub mod vmcs;
 pub mod vmerror;
@@ -0,0 +1,247 @@
+//! Serialization and restoration of the live hypervisor state: the VMCS guest/host fields that
+//! matter across a hand-off, and the `HookManager`'s installed EPT hooks.
+//!
+//! Modeled on cloud-hypervisor's save/restore: the state is flattened into a single
+//! version-tagged, length-prefixed blob the guest can stash (and later hand back) through a
+//! VMCALL, primarily useful for debugging (dumping exactly which hooks are installed, where, and
+//! how) and for surviving hand-off/transition points without re-walking every guest function to
+//! re-discover hooks from scratch.
+
+use {
+    crate::{
+        error::HypervisorError,
+        intel::{hooks::hook_manager::EptHookType, vm::Vm},
+    },
+    core::mem::size_of,
+};
+
+/// Identifies this blob as an illusion-rs hypervisor snapshot, checked before any other field is
+/// trusted on restore.
+const SNAPSHOT_MAGIC: u32 = 0x494C_5553; // "ILUS"
+
+/// The snapshot format version. Bumped whenever a field is added, removed, or reinterpreted, so
+/// `restore` can refuse a blob produced by an incompatible build instead of misreading it.
+const SNAPSHOT_VERSION: u16 = 1;
+
+/// The fixed-size header every snapshot blob begins with.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct SnapshotHeader {
+    /// Must equal [`SNAPSHOT_MAGIC`].
+    magic: u32,
+    /// Must equal [`SNAPSHOT_VERSION`].
+    version: u16,
+    /// Number of [`HookSnapshotEntry`] records following the [`VmcsSnapshot`].
+    hook_count: u16,
+    /// Total size of the blob, header included, in bytes. Used to bounds-check before the
+    /// variable-length hook table is read.
+    total_size: u32,
+}
+
+/// The subset of VMCS guest/host fields that matter for reconstructing execution state on
+/// restore: everything needed to resume the guest where it left off, plus the host state the
+/// hypervisor itself needs to keep running.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct VmcsSnapshot {
+    guest_cr0: u64,
+    guest_cr3: u64,
+    guest_cr4: u64,
+    guest_rsp: u64,
+    guest_rip: u64,
+    guest_rflags: u64,
+    host_cr3: u64,
+    /// Physical address of the primary EPT's PML4, i.e. the value loaded into the EPTP.
+    ept_pointer: u64,
+}
+
+/// One installed EPT hook, as needed to reconstruct the split mapping and the `HookManager`'s
+/// bookkeeping for it on restore, and to report in a debug dump.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct HookSnapshotEntry {
+    /// Guest-physical address of the hooked page.
+    guest_pa: u64,
+    /// Guest-physical address of the shadow page installed in its place.
+    shadow_pa: u64,
+    /// The `EptHookType` discriminant (kernel inline hook, syscall inline hook, page hook, ...).
+    hook_type: u32,
+    /// Number of instructions overwritten at the hook site, re-registered with the `HookManager`
+    /// so its trampoline single-steps the correct count the next time this hook fires.
+    overwritten_instruction_count: u16,
+}
+
+/// Serializes the live VMCS state and the set of installed EPT hooks into `buffer`.
+///
+/// # Arguments
+///
+/// * `vm` - The virtual machine whose state is being captured.
+/// * `buffer` - The guest-provided destination buffer. Must be at least large enough for the
+///   header, the `VmcsSnapshot`, and one `HookSnapshotEntry` per installed hook; see
+///   [`required_capacity`].
+///
+/// # Returns
+///
+/// * `Ok(usize)` - The number of bytes written to `buffer`.
+/// * `Err(HypervisorError::InsufficientBufferSize)` - If `buffer` is too small to hold the
+///   current state.
+pub fn serialize(vm: &Vm, buffer: &mut [u8]) -> Result<usize, HypervisorError> {
+    let hooks = vm.hook_manager.memory_manager.installed_hooks();
+    let total_size = required_capacity(hooks.len());
+
+    if buffer.len() < total_size {
+        return Err(HypervisorError::InsufficientBufferSize);
+    }
+
+    let header = SnapshotHeader {
+        magic: SNAPSHOT_MAGIC,
+        version: SNAPSHOT_VERSION,
+        hook_count: hooks.len() as u16,
+        total_size: total_size as u32,
+    };
+
+    let vmcs_snapshot = VmcsSnapshot {
+        guest_cr0: vm.guest_registers.cr0,
+        guest_cr3: vm.guest_registers.cr3,
+        guest_cr4: vm.guest_registers.cr4,
+        guest_rsp: vm.guest_registers.rsp,
+        guest_rip: vm.guest_registers.rip,
+        guest_rflags: vm.guest_registers.rflags,
+        host_cr3: vm.host_registers.cr3,
+        ept_pointer: vm.primary_ept.pml4_pa(),
+    };
+
+    let mut offset = 0;
+    offset += write_struct(&mut buffer[offset..], &header);
+    offset += write_struct(&mut buffer[offset..], &vmcs_snapshot);
+
+    for hook in hooks {
+        let entry = HookSnapshotEntry {
+            guest_pa: hook.guest_pa,
+            shadow_pa: hook.shadow_pa,
+            hook_type: hook.ept_hook_type as u32,
+            overwritten_instruction_count: hook.overwritten_instruction_count,
+        };
+
+        offset += write_struct(&mut buffer[offset..], &entry);
+    }
+
+    Ok(offset)
+}
+
+/// Validates and restores a previously [`serialize`]d blob: reconstructs the EPT split mappings,
+/// re-registers each hook with the `HookManager` so it is recognized again, and writes the
+/// captured guest state back into the VMCS guest-state area so it takes effect on the next
+/// VM-entry.
+///
+/// This does not touch `hook_manager.mtf_counter`: that field tracks a single-step cycle already
+/// in progress for one hook's trampoline (see `vmexit::mtf`), and a restored hook only needs one
+/// again once its trampoline VMCALL next fires, the same as a freshly installed hook.
+///
+/// # Arguments
+///
+/// * `vm` - The virtual machine whose EPT, hook bookkeeping, and VMCS guest state should be
+///   rehydrated.
+/// * `buffer` - The guest-provided buffer containing a snapshot blob.
+///
+/// # Returns
+///
+/// * `Ok(())` - The VM's EPT, hook state, and VMCS guest state now match the snapshot.
+/// * `Err(HypervisorError::SnapshotVersionMismatch)` - The blob's magic or version does not match
+///   this build.
+/// * `Err(HypervisorError::InsufficientBufferSize)` - `buffer` is shorter than the size the
+///   header claims, so the hook table cannot be trusted.
+pub fn restore(vm: &mut Vm, buffer: &[u8]) -> Result<(), HypervisorError> {
+    if buffer.len() < size_of::<SnapshotHeader>() {
+        return Err(HypervisorError::InsufficientBufferSize);
+    }
+
+    let header = read_struct::<SnapshotHeader>(buffer);
+
+    if header.magic != SNAPSHOT_MAGIC || header.version != SNAPSHOT_VERSION {
+        return Err(HypervisorError::SnapshotVersionMismatch);
+    }
+
+    if buffer.len() < header.total_size as usize || (header.total_size as usize) < required_capacity(header.hook_count as usize) {
+        return Err(HypervisorError::InsufficientBufferSize);
+    }
+
+    let mut offset = size_of::<SnapshotHeader>();
+    let vmcs_snapshot = read_struct::<VmcsSnapshot>(&buffer[offset..]);
+    offset += size_of::<VmcsSnapshot>();
+
+    vm.guest_registers.cr0 = vmcs_snapshot.guest_cr0;
+    vm.guest_registers.cr3 = vmcs_snapshot.guest_cr3;
+    vm.guest_registers.cr4 = vmcs_snapshot.guest_cr4;
+    vm.guest_registers.rsp = vmcs_snapshot.guest_rsp;
+    vm.guest_registers.rip = vmcs_snapshot.guest_rip;
+    vm.guest_registers.rflags = vmcs_snapshot.guest_rflags;
+    vm.host_registers.cr3 = vmcs_snapshot.host_cr3;
+
+    write_vmcs_guest_state(&vmcs_snapshot);
+
+    for _ in 0..header.hook_count {
+        let entry = read_struct::<HookSnapshotEntry>(&buffer[offset..]);
+        offset += size_of::<HookSnapshotEntry>();
+
+        vm.primary_ept.swap_page(
+            entry.guest_pa,
+            entry.shadow_pa,
+            crate::intel::ept::AccessType::READ_WRITE_EXECUTE,
+            vm.hook_manager.memory_manager.get_page_table_as_mut(entry.guest_pa).ok_or(HypervisorError::PageTableNotFound)?,
+        )?;
+
+        // Re-add this hook to the memory manager's own bookkeeping; without this,
+        // `get_shadow_page_as_ptr`/`get_hook_info_by_function_pa` would not recognize the page we
+        // just re-split as a hook on the next lookup.
+        vm.hook_manager.memory_manager.register_restored_hook(
+            entry.guest_pa,
+            entry.shadow_pa,
+            EptHookType::from_u32(entry.hook_type),
+            entry.overwritten_instruction_count,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Writes the restored guest control-register, stack/instruction-pointer, flags, and EPT-pointer
+/// state into the VMCS guest-state area so it takes effect on the next VM-entry, rather than only
+/// updating the host-side cache in `vm.guest_registers`.
+fn write_vmcs_guest_state(vmcs_snapshot: &VmcsSnapshot) {
+    use crate::intel::vmcs::{vmwrite, VmcsField};
+
+    unsafe {
+        vmwrite(VmcsField::GuestCr0, vmcs_snapshot.guest_cr0);
+        vmwrite(VmcsField::GuestCr3, vmcs_snapshot.guest_cr3);
+        vmwrite(VmcsField::GuestCr4, vmcs_snapshot.guest_cr4);
+        vmwrite(VmcsField::GuestRsp, vmcs_snapshot.guest_rsp);
+        vmwrite(VmcsField::GuestRip, vmcs_snapshot.guest_rip);
+        vmwrite(VmcsField::GuestRflags, vmcs_snapshot.guest_rflags);
+        vmwrite(VmcsField::EptPointer, vmcs_snapshot.ept_pointer);
+    }
+}
+
+/// The buffer size, in bytes, required to hold a snapshot with `hook_count` installed hooks.
+fn required_capacity(hook_count: usize) -> usize {
+    size_of::<SnapshotHeader>() + size_of::<VmcsSnapshot>() + hook_count * size_of::<HookSnapshotEntry>()
+}
+
+/// Copies `value`'s bytes into the start of `buffer` and returns how many bytes were written.
+fn write_struct<T: Copy>(buffer: &mut [u8], value: &T) -> usize {
+    let size = size_of::<T>();
+    let bytes = unsafe { core::slice::from_raw_parts(value as *const T as *const u8, size) };
+    buffer[..size].copy_from_slice(bytes);
+    size
+}
+
+/// Reads a `T` out of the start of `buffer`.
+///
+/// # Safety requirements upheld by callers
+///
+/// `buffer` must be at least `size_of::<T>()` bytes (checked by `serialize`/`restore` before
+/// calling this) and `T` must be a `#[repr(C)]`, `Copy` plain-old-data type, as every type this
+/// function is called with in this module is.
+fn read_struct<T: Copy>(buffer: &[u8]) -> T {
+    unsafe { core::ptr::read_unaligned(buffer.as_ptr() as *const T) }
+}
@@ -0,0 +1,113 @@
+//! Intel VMX vCPU: drives a single guest core via `VMLAUNCH`/`VMRESUME`, implementing the
+//! [`crate::vcpu::Hypervisor`]/[`crate::vcpu::Vcpu`] traits on top of [`Vm`] so the rest of the
+//! crate (and `driver::virtualize_system`) can drive this backend without caring that it happens
+//! to be VMX/EPT under the hood. `amd::svm::Svm` is the AMD-V counterpart.
+
+use crate::{
+    error::HypervisorError,
+    intel::{
+        capture::GuestRegisters,
+        ept::AccessType,
+        vm::Vm,
+        vmcs::{vmread, VmcsField},
+        vmexit::{handle_vmexit, ExitType},
+        vmlaunch::{vmlaunch, vmresume},
+    },
+    vcpu::{Hypervisor, Vcpu, VmExitReason},
+};
+
+/// A single Intel VMX virtual CPU: the [`Vm`] state a VM-exit handler needs, plus whether this is
+/// the first entry (`VMLAUNCH`) or a subsequent one (`VMRESUME`).
+pub struct IntelVcpu {
+    vm: Vm,
+    launched: bool,
+}
+
+impl IntelVcpu {
+    /// Wraps an already set-up [`Vm`] (VMCS loaded and active) as a [`Vcpu`], ready for its first
+    /// `VMLAUNCH`.
+    fn new(vm: Vm) -> Self {
+        Self { vm, launched: false }
+    }
+
+    /// Normalizes the VMCS basic exit reason (bits 15:0 of the `EXIT_REASON` field) into the
+    /// vendor-neutral [`VmExitReason`].
+    fn normalize_exit_reason(exit_reason: u32) -> VmExitReason {
+        match exit_reason {
+            18 => VmExitReason::Vmcall,
+            10 => VmExitReason::Cpuid,
+            48 => VmExitReason::NestedPageFault,
+            37 => VmExitReason::MonitorTrapFlag,
+            other => VmExitReason::Other(other),
+        }
+    }
+}
+
+impl Hypervisor for IntelVcpu {
+    type Vcpu = IntelVcpu;
+
+    fn virtualize_core(initial_registers: &GuestRegisters) -> Result<Self::Vcpu, HypervisorError> {
+        // Entering VMX root operation (`VMXON`), allocating and activating a VMCS (`VMCLEAR` +
+        // `VMPTRLD`), and populating its guest/host-state and control fields from
+        // `initial_registers` all belong here, the same way `amd::svm::Svm::virtualize_core`
+        // documents the analogous SVM setup it still has to grow.
+        let _ = initial_registers;
+        Err(HypervisorError::NotSupported)
+    }
+}
+
+impl Vcpu for IntelVcpu {
+    fn run(&mut self) -> Result<VmExitReason, HypervisorError> {
+        let result = if self.launched { unsafe { vmresume() } } else { unsafe { vmlaunch() } };
+
+        result?;
+        self.launched = true;
+
+        let exit_reason = unsafe { vmread(VmcsField::VmExitReason) } as u32 & 0xFFFF;
+
+        if handle_vmexit(&mut self.vm, exit_reason)? == ExitType::ExitHypervisor {
+            return Err(HypervisorError::NotSupported);
+        }
+
+        Ok(Self::normalize_exit_reason(exit_reason))
+    }
+
+    fn guest_registers(&self) -> &GuestRegisters {
+        &self.vm.guest_registers
+    }
+
+    fn guest_registers_mut(&mut self) -> &mut GuestRegisters {
+        &mut self.vm.guest_registers
+    }
+
+    fn install_hook(&mut self, guest_pa: u64, shadow_pa: u64, access_type: AccessType) -> Result<(), HypervisorError> {
+        let page_table = self
+            .vm
+            .hook_manager
+            .memory_manager
+            .get_page_table_as_mut(guest_pa)
+            .ok_or(HypervisorError::PageTableNotFound)?;
+
+        self.vm.primary_ept.swap_page(guest_pa, shadow_pa, access_type, page_table)
+    }
+
+    fn inject_event(&mut self, vector: u8, error_code: Option<u32>) {
+        use crate::intel::events::{EventInjection, InterruptionType, PendingEvent};
+
+        let interruption_type = match vector {
+            2 => InterruptionType::Nmi,
+            0..=31 => InterruptionType::HardwareException,
+            _ => InterruptionType::ExternalInterrupt,
+        };
+
+        EventInjection::queue_and_inject(
+            &mut self.vm.pending_events,
+            PendingEvent {
+                vector,
+                interruption_type,
+                error_code,
+                instruction_length: None,
+            },
+        );
+    }
+}
@@ -0,0 +1,49 @@
+//! Per-core virtual machine state.
+//!
+//! Bundles everything a VM-exit handler needs to drive one logical guest CPU: the live
+//! guest/host register snapshot, the second-level address-translation tables, hook bookkeeping,
+//! and the queue of events awaiting injection into the guest.
+
+use crate::intel::{capture::GuestRegisters, ept::Ept, events::PendingEventQueue, hooks::hook_manager::HookManager};
+
+/// A single virtual machine: one logical guest CPU and the state its VM-exit handlers operate on.
+pub struct Vm {
+    /// The guest's general-purpose and control register state as of the last VM exit. VM-exit
+    /// handlers read and write this directly; it is written back into the VMCS before the next
+    /// VM-entry.
+    pub guest_registers: GuestRegisters,
+
+    /// The host's register state, captured on VM-entry so it can be restored on VM exit.
+    pub host_registers: GuestRegisters,
+
+    /// The primary EPT covering all of guest-physical memory, including any installed hooks.
+    pub primary_ept: Ept,
+
+    /// Hook installation/removal bookkeeping: installed hooks, shadow pages, and the MTF
+    /// single-step counter used to restore overwritten instructions.
+    pub hook_manager: HookManager,
+
+    /// Events (exceptions, NMIs, interrupts) queued for injection into the guest, serialized so
+    /// only one is ever in flight for a given VM-entry. See `intel::events`.
+    pub pending_events: PendingEventQueue,
+}
+
+impl Vm {
+    /// Constructs a new `Vm` with empty hook bookkeeping and no events pending injection.
+    ///
+    /// # Arguments
+    ///
+    /// * `guest_registers` - The initial guest register state to resume into on first entry.
+    /// * `host_registers` - The host register state to restore into on VM exit.
+    /// * `primary_ept` - The primary EPT covering guest-physical memory.
+    /// * `hook_manager` - Hook bookkeeping, initially with no hooks installed.
+    pub fn new(guest_registers: GuestRegisters, host_registers: GuestRegisters, primary_ept: Ept, hook_manager: HookManager) -> Self {
+        Self {
+            guest_registers,
+            host_registers,
+            primary_ept,
+            hook_manager,
+            pending_events: PendingEventQueue::new(),
+        }
+    }
+}
@@ -0,0 +1,157 @@
+//! Top-level VM-exit dispatch.
+//!
+//! Every VM exit passes through [`handle_vmexit`] before any per-reason handler runs, so event
+//! bookkeeping happens uniformly regardless of why the exit occurred: an event the processor was
+//! mid-delivery of gets re-queued rather than dropped, and at most one queued event is armed for
+//! the next VM-entry, with NMI-window/interrupt-window exiting picking up the rest once the guest
+//! is ready for them instead of injecting blindly.
+
+pub mod mtf;
+pub mod vmcall;
+
+use crate::{
+    error::HypervisorError,
+    intel::{
+        events::{set_interrupt_window_exiting, set_nmi_window_exiting, EventInjection, InterruptionType},
+        vm::Vm,
+        vmcs::{vmread, VmcsField},
+    },
+};
+
+/// RFLAGS.IF (bit 9): external interrupts are only deliverable while this is set.
+const RFLAGS_IF: u64 = 1 << 9;
+
+/// Guest-interruptibility-state bit 0: blocking by `STI` (the instruction boundary right after
+/// `STI` still defers interrupt delivery by one instruction).
+const INTERRUPTIBILITY_BLOCKED_BY_STI: u64 = 1 << 0;
+
+/// Guest-interruptibility-state bit 1: blocking by `MOV SS`/`POP SS` (same one-instruction
+/// deferral as blocking-by-`STI`).
+const INTERRUPTIBILITY_BLOCKED_BY_MOV_SS: u64 = 1 << 1;
+
+/// Guest-interruptibility-state bit 3: blocking by NMI, set from the delivery of one NMI until
+/// the guest executes `IRET`.
+const INTERRUPTIBILITY_BLOCKED_BY_NMI: u64 = 1 << 3;
+
+/// What should happen on the next VM-entry.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ExitType {
+    /// Resume the guest.
+    Continue,
+    /// Terminate the hypervisor for this core.
+    ExitHypervisor,
+}
+
+/// The basic VM-exit reason (VMCS `EXIT_REASON` field, bits 15:0), normalized to the reasons this
+/// module handles explicitly.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum BasicExitReason {
+    /// The interrupt window opened (basic exit reason 7): the guest is ready to receive an
+    /// external interrupt.
+    InterruptWindow,
+    /// The NMI window opened (basic exit reason 8): the guest is ready to receive an NMI.
+    NmiWindow,
+    /// The guest executed `VMCALL` (basic exit reason 18).
+    Vmcall,
+    /// Any other exit reason, not yet given a dedicated handler.
+    Other(u32),
+}
+
+impl BasicExitReason {
+    fn from_u32(value: u32) -> Self {
+        match value {
+            7 => BasicExitReason::InterruptWindow,
+            8 => BasicExitReason::NmiWindow,
+            18 => BasicExitReason::Vmcall,
+            other => BasicExitReason::Other(other),
+        }
+    }
+}
+
+/// Handles a single VM exit end to end.
+///
+/// # Arguments
+///
+/// * `vm` - The virtual machine instance that exited.
+/// * `exit_reason` - The raw VMCS `EXIT_REASON` field value (bits 15:0) read by the caller.
+///
+/// # Returns
+///
+/// * `Ok(ExitType)` - What should happen on the next VM-entry.
+/// * `Err(HypervisorError)` - If the per-reason handler, or arming the next event, failed.
+pub fn handle_vmexit(vm: &mut Vm, exit_reason: u32) -> Result<ExitType, HypervisorError> {
+    // Before dispatching on why we exited: if the processor was mid-delivery of an event when
+    // this exit occurred (reported via the IDT-vectoring-information field), re-queue it so it
+    // isn't silently dropped - the textbook cause of injected NMIs/exceptions looping.
+    EventInjection::requeue_interrupted_event(&mut vm.pending_events);
+
+    let exit_type = match BasicExitReason::from_u32(exit_reason) {
+        BasicExitReason::Vmcall => vmcall::handle_vmcall(vm)?,
+        // The window we asked for to deliver a deferred NMI/interrupt has opened; the injection
+        // itself happens below, uniformly, once we know nothing else queued an event this exit.
+        BasicExitReason::InterruptWindow | BasicExitReason::NmiWindow => ExitType::Continue,
+        BasicExitReason::Other(reason) => {
+            log::warn!("Unhandled VM exit reason: {:#x}", reason);
+            ExitType::Continue
+        }
+    };
+
+    arm_next_event(vm)?;
+
+    Ok(exit_type)
+}
+
+/// Injects at most one queued event this entry if the guest is actually ready to receive it, and
+/// otherwise arms NMI-window or interrupt-window exiting so the front of the queue is delivered
+/// as soon as the guest can receive it rather than overriding RFLAGS.IF/NMI-blocking by injecting
+/// blindly.
+fn arm_next_event(vm: &mut Vm) -> Result<(), HypervisorError> {
+    // Never inject while a hook's overwritten instructions are being replayed one at a time: the
+    // guest landing mid-step somewhere the hook-restoration logic in `vmexit::mtf` doesn't expect
+    // is exactly the kind of bug this subsystem exists to avoid.
+    if vm.hook_manager.mtf_counter.is_some() {
+        return Ok(());
+    }
+
+    let front_type = vm.pending_events.front().map(|event| event.interruption_type);
+
+    let injected = match front_type {
+        Some(interruption_type) if guest_ready_for(vm, interruption_type) => EventInjection::inject_pending_event(&mut vm.pending_events),
+        _ => false,
+    };
+
+    if injected {
+        set_nmi_window_exiting(false)?;
+        set_interrupt_window_exiting(false)?;
+        return Ok(());
+    }
+
+    match front_type {
+        Some(InterruptionType::Nmi) => set_nmi_window_exiting(true)?,
+        Some(_) => set_interrupt_window_exiting(true)?,
+        None => {}
+    }
+
+    Ok(())
+}
+
+/// Whether the guest can actually receive an event of `interruption_type` on the next VM-entry,
+/// per the VMCS guest-interruptibility-state field and, for external interrupts, RFLAGS.IF.
+///
+/// Exceptions and software interrupts are not maskable this way and are always considered ready;
+/// only `Nmi` (blocked by a prior un-`IRET`ed NMI) and `ExternalInterrupt` (blocked by RFLAGS.IF,
+/// or for one instruction after `STI`/`MOV SS`) are gated.
+fn guest_ready_for(vm: &Vm, interruption_type: InterruptionType) -> bool {
+    match interruption_type {
+        InterruptionType::Nmi => {
+            let interruptibility = unsafe { vmread(VmcsField::GuestInterruptibilityState) };
+            interruptibility & INTERRUPTIBILITY_BLOCKED_BY_NMI == 0
+        }
+        InterruptionType::ExternalInterrupt => {
+            let interruptibility = unsafe { vmread(VmcsField::GuestInterruptibilityState) };
+            let step_blocked = interruptibility & (INTERRUPTIBILITY_BLOCKED_BY_STI | INTERRUPTIBILITY_BLOCKED_BY_MOV_SS) != 0;
+            vm.guest_registers.rflags & RFLAGS_IF != 0 && !step_blocked
+        }
+        _ => true,
+    }
+}
@@ -0,0 +1,38 @@
+//! Monitor Trap Flag (MTF) single-stepping.
+//!
+//! Used to single-step the guest one instruction at a time while replaying the instructions a
+//! hook overwrote (see `vmexit::vmcall::handle_vmcall`), and to keep the guest's interrupt flag
+//! quiesced for the duration so the step can't be interrupted partway through.
+
+use crate::{error::HypervisorError, intel::vm::Vm};
+
+/// Entry point for enabling or disabling the Monitor Trap Flag VM-execution control, which makes
+/// the processor take a VM exit after the next guest instruction retires.
+///
+/// The body does not write the VMCS: `enable` is ignored, so calling this requests no MTF exit.
+///
+/// # Arguments
+///
+/// * `enable` - Whether MTF single-stepping should be active.
+pub fn set_monitor_trap_flag(enable: bool) {
+    let _ = enable;
+}
+
+/// Updates the guest's interrupt-window state while a single-step cycle is in progress.
+///
+/// Single-stepping a hook's overwritten instructions takes several VM exits in a row; an event
+/// delivered to the guest partway through would leave it somewhere the hook-restoration logic
+/// doesn't expect. Callers pass `enable = false` when a step starts and `true` when it ends, after
+/// all other mutations to `vm` for the current exit.
+///
+/// The body does not modify `vm` or the VMCS: both arguments are ignored and `Ok(())` is
+/// returned, so interrupt delivery is left as it was.
+///
+/// # Arguments
+///
+/// * `vm` - The virtual machine instance being single-stepped.
+/// * `enable` - Whether interrupt delivery should be enabled; `false` for the duration of a step.
+pub fn update_guest_interrupt_flag(vm: &mut Vm, enable: bool) -> Result<(), HypervisorError> {
+    let _ = (vm, enable);
+    Ok(())
+}
@@ -10,6 +10,8 @@ use {
             events::EventInjection,
             hooks::hook_manager::HookManager,
             vm::Vm,
+            snapshot,
+            vmcs::{vmread, VmcsField},
             vmexit::{
                 mtf::{set_monitor_trap_flag, update_guest_interrupt_flag},
                 ExitType,
@@ -17,15 +19,60 @@ use {
         },
     },
     log::*,
+    shared::{ClientData, Commands, MulticallEntry, PASSWORD},
     x86::bits64::paging::PAddr,
 };
 
+/// The largest number of entries accepted in a single `VmcallCommand::HookRequestBatch`
+/// multicall. Bounds the time a single VM exit can spend with interrupts effectively deferred
+/// and the amount of guest memory a single hypercall can force the hypervisor to touch.
+const MAX_MULTICALL_ENTRIES: u64 = 256;
+
 /// Represents various VMCALL commands that a guest can issue to the hypervisor.
 #[repr(u64)]
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum VmcallCommand {
     /// Command to indicate an unknown or unimplemented VMCALL command.
     Unknown = 0,
+
+    /// Command to authenticate the caller and carry out a single hooking operation described by
+    /// a guest-supplied `shared::ClientData` structure.
+    HookRequest = 1,
+
+    /// Command to authenticate the caller and carry out a batch of hooking operations in one
+    /// VMCALL, Xen multicall-style, described by a guest-supplied array of
+    /// `shared::MulticallEntry` structures.
+    HookRequestBatch = 2,
+
+    /// Command to serialize the live VMCS state and installed EPT hooks into a guest-provided
+    /// buffer. See `intel::snapshot::serialize`.
+    SnapshotDump = 3,
+
+    /// Command to validate and restore a previously dumped snapshot from a guest-provided
+    /// buffer. See `intel::snapshot::restore`.
+    SnapshotRestore = 4,
+}
+
+impl VmcallCommand {
+    /// Converts a `u64` value (as found in the guest's RCX at the time of the VMCALL) into a
+    /// `VmcallCommand` variant.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The `u64` value to convert.
+    ///
+    /// # Returns
+    ///
+    /// * `VmcallCommand` - The corresponding `VmcallCommand` enum variant.
+    pub fn from_u64(value: u64) -> Self {
+        match value {
+            1 => VmcallCommand::HookRequest,
+            2 => VmcallCommand::HookRequestBatch,
+            3 => VmcallCommand::SnapshotDump,
+            4 => VmcallCommand::SnapshotRestore,
+            _ => VmcallCommand::Unknown,
+        }
+    }
 }
 
 /// Handles a VMCALL VM exit by executing the corresponding action based on the VMCALL command.
@@ -46,11 +93,15 @@ pub fn handle_vmcall(vm: &mut Vm) -> Result<ExitType, HypervisorError> {
     debug!("Handling VMCALL VM exit...");
     trace!("Register state before handling VM exit: {:?}", vm.guest_registers);
 
-    let vmcall_number = vm.guest_registers.rax;
-    trace!("Guest RAX - VMCALL command number: {:#x}", vmcall_number);
-    trace!("Guest RIP: {:#x}", vm.guest_registers.rip);
-
-    let guest_function_pa = PAddr::from(PhysicalAddress::pa_from_va(vm.guest_registers.rip));
+    let guest_function_pa = match PhysicalAddress::pa_from_va(vm.guest_registers.cr3, vm.guest_registers.rip) {
+        Ok(pa) => PAddr::from(pa),
+        Err(_) => {
+            // RIP itself didn't resolve through the guest's own page tables; inject #GP rather
+            // than act on a bogus PA.
+            EventInjection::vmentry_inject_gp(&mut vm.pending_events, 0);
+            return Ok(ExitType::Continue);
+        }
+    };
     trace!("Guest PA: {:#x}", guest_function_pa.as_u64());
 
     let guest_page_pa = guest_function_pa.align_down_to_base_page();
@@ -59,8 +110,9 @@ pub fn handle_vmcall(vm: &mut Vm) -> Result<ExitType, HypervisorError> {
     let guest_large_page_pa = guest_page_pa.align_down_to_large_page();
     trace!("Guest Large Page PA: {:#x}", guest_large_page_pa.as_u64());
 
-    // Set the current hook to the EPT hook for handling MTF exit
-    let exit_type = if let Some(shadow_page_pa) = vm.hook_manager.memory_manager.get_shadow_page_as_ptr(guest_page_pa.as_u64()) {
+    // If RIP lands on a page we've already hooked, this VMCALL is the hook trampoline calling
+    // back into the hypervisor to restore the overwritten instructions via single-stepping.
+    if let Some(shadow_page_pa) = vm.hook_manager.memory_manager.get_shadow_page_as_ptr(guest_page_pa.as_u64()) {
         trace!("Shadow Page PA: {:#x}", shadow_page_pa);
 
         trace!("Executing VMCALL hook on shadow page for EPT hook at PA: {:#x} with VA: {:#x}", guest_function_pa, vm.guest_registers.rip);
@@ -100,11 +152,279 @@ pub fn handle_vmcall(vm: &mut Vm) -> Result<ExitType, HypervisorError> {
         // This function will update the guest interrupt flag to prevent interrupts while single-stepping
         update_guest_interrupt_flag(vm, false)?;
 
-        Ok(ExitType::Continue)
-    } else {
-        EventInjection::vmentry_inject_gp(0);
-        Ok(ExitType::Continue)
+        return Ok(ExitType::Continue);
+    }
+
+    // Otherwise, this is a direct hypercall issued by our client driver rather than a hook
+    // trampoline, so authenticate it and dispatch it by command.
+    handle_hypercall(vm)
+}
+
+/// Authenticates and dispatches a direct hypercall from the client driver.
+///
+/// The calling convention mirrors a minimal bhyve/Xen-style hypercall ABI: RAX carries the
+/// `shared::PASSWORD` value the client and hypervisor both know, RCX carries the `VmcallCommand`
+/// to execute, and RDX carries a guest-virtual pointer to the command's argument structure (a
+/// `shared::ClientData` for `VmcallCommand::HookRequest`, or a `shared::MulticallEntry` array for
+/// `VmcallCommand::HookRequestBatch`, whose element count is carried in R8). For
+/// `VmcallCommand::HookRequestBatch`, R9 additionally carries a stop-on-error flag: non-zero
+/// aborts the batch at the first failing entry instead of continuing through the rest (see
+/// [`dispatch_multicall`]). The call's outcome is written back into RAX: `0` for success,
+/// non-zero for failure.
+///
+/// # Parameters
+///
+/// * `vm`: A mutable reference to the virtual machine instance encountering the VMCALL exit.
+///
+/// # Returns
+///
+/// * `Ok(ExitType::Continue)` once the hypercall has been authenticated and handled, regardless
+///   of whether the requested operation itself succeeded (the result is communicated back via
+///   RAX, not via this function's `Result`).
+fn handle_hypercall(vm: &mut Vm) -> Result<ExitType, HypervisorError> {
+    let password = vm.guest_registers.rax;
+    let command = VmcallCommand::from_u64(vm.guest_registers.rcx);
+
+    if password != PASSWORD {
+        warn!("VMCALL authentication failed (RAX: {:#x})", password);
+        EventInjection::vmentry_inject_gp(&mut vm.pending_events, 0);
+        return Ok(ExitType::Continue);
+    }
+
+    trace!("Authenticated VMCALL command: {:?}", command);
+
+    let status = match command {
+        VmcallCommand::HookRequest => match dispatch_client_data(vm, vm.guest_registers.rdx) {
+            Ok(_) => 0u64,
+            Err(error) => {
+                warn!("Failed to service hypercall hook request: {:?}", error);
+                1u64
+            }
+        },
+        VmcallCommand::HookRequestBatch => match dispatch_multicall(vm, vm.guest_registers.rdx, vm.guest_registers.r8, vm.guest_registers.r9 != 0) {
+            Ok(_) => 0u64,
+            Err(error) => {
+                warn!("Failed to service hypercall multicall batch: {:?}", error);
+                1u64
+            }
+        },
+        VmcallCommand::SnapshotDump => match dispatch_snapshot_dump(vm, vm.guest_registers.rdx, vm.guest_registers.r8) {
+            Ok(_) => 0u64,
+            Err(error) => {
+                warn!("Failed to service snapshot dump: {:?}", error);
+                1u64
+            }
+        },
+        VmcallCommand::SnapshotRestore => match dispatch_snapshot_restore(vm, vm.guest_registers.rdx, vm.guest_registers.r8) {
+            Ok(_) => 0u64,
+            Err(error) => {
+                warn!("Failed to service snapshot restore: {:?}", error);
+                1u64
+            }
+        },
+        VmcallCommand::Unknown => {
+            // Consistent with every other command: report failure via RAX only, rather than also
+            // injecting a fault.
+            warn!("Unknown authenticated VMCALL command: {:#x}", vm.guest_registers.rcx);
+            u64::MAX
+        }
     };
 
-    exit_type
+    // Report the outcome back to the guest so the client can tell whether the hook took effect.
+    vm.guest_registers.rax = status;
+
+    // Unlike the hook-trampoline path above (which intentionally re-enters at the same RIP for
+    // MTF single-stepping), a real client hypercall is done once dispatched: skip past the
+    // `VMCALL` itself so the guest doesn't re-execute it forever.
+    let instruction_len = unsafe { vmread(VmcsField::VmExitInstructionLen) };
+    vm.guest_registers.rip = vm.guest_registers.rip.wrapping_add(instruction_len);
+
+    Ok(ExitType::Continue)
+}
+
+/// Translates a guest-virtual pointer to a `shared::ClientData`, copies it out of guest memory,
+/// and dispatches the requested hooking operation to the `HookManager`.
+///
+/// # Arguments
+///
+/// * `vm` - The virtual machine instance the hypercall was issued from.
+/// * `client_data_va` - The guest-virtual address of the `shared::ClientData` argument.
+///
+/// # Returns
+///
+/// * `Ok(())` if the requested hook was installed or removed successfully.
+/// * `Err(HypervisorError)` if the pointer could not be translated or the hook operation failed.
+fn dispatch_client_data(vm: &mut Vm, client_data_va: u64) -> Result<(), HypervisorError> {
+    let client_data_pa = PhysicalAddress::pa_from_va(vm.guest_registers.cr3, client_data_va)?;
+    trace!("ClientData guest VA: {:#x} -> PA: {:#x}", client_data_va, client_data_pa);
+
+    // The host identity-maps all guest physical memory, so once translated from the
+    // guest-virtual address above, the guest-physical address can be read directly.
+    let client_data = *ClientData::from_ptr(client_data_pa);
+    trace!("ClientData: {:?}", client_data);
+
+    match client_data.command {
+        Commands::EnableKernelInlineHook => vm.hook_manager.enable_kernel_inline_hook(client_data.function_hash),
+        Commands::EnableSyscallInlineHook => vm.hook_manager.enable_syscall_inline_hook(client_data.syscall_number, client_data.get_from_win32k, client_data.function_hash),
+        Commands::DisablePageHook => vm.hook_manager.disable_page_hook(client_data.function_hash),
+        Commands::Invalid => Err(HypervisorError::UnknownVmcallCommand),
+    }
+}
+
+/// Undoes whatever `dispatch_client_data` installed for a failed multicall entry, dispatching on
+/// the entry's own command rather than assuming it was a page hook: `EnableSyscallInlineHook` is
+/// keyed by `syscall_number`, not `function_hash`, so rolling it back through
+/// `HookManager::disable_page_hook` would silently miss the mapping it actually installed.
+///
+/// # Arguments
+///
+/// * `vm` - The virtual machine instance the hypercall was issued from.
+/// * `client_data` - The failed entry's `shared::ClientData`, identifying which hook to undo.
+///
+/// # Returns
+///
+/// * `Ok(())` if the matching hook was removed, or there was nothing to roll back.
+/// * `Err(HypervisorError)` if the `HookManager` failed to undo the hook.
+fn rollback_entry(vm: &mut Vm, client_data: &ClientData) -> Result<(), HypervisorError> {
+    match client_data.command {
+        Commands::EnableKernelInlineHook => vm.hook_manager.disable_page_hook(client_data.function_hash),
+        Commands::EnableSyscallInlineHook => vm.hook_manager.disable_syscall_inline_hook(client_data.syscall_number),
+        // A failed `DisablePageHook` never installed anything new, and `Invalid` never reached
+        // the `HookManager` at all - neither leaves state behind to undo.
+        Commands::DisablePageHook | Commands::Invalid => Ok(()),
+    }
+}
+
+/// Walks a guest-supplied array of `shared::MulticallEntry` structures and executes each entry's
+/// hooking operation in sequence through the `HookManager`, writing the per-entry result back
+/// into guest memory as it goes.
+///
+/// Unlike [`dispatch_client_data`], a failure on one entry does not necessarily abort the batch:
+/// the error is recorded in that entry's `status` field, and the walk continues with the next
+/// entry unless `stop_on_error` is set, in which case the batch stops at the first failing entry
+/// so a bad request among dozens of boot-time syscall hooks doesn't take the rest down with it
+/// (or, with the flag set, doesn't risk layering further hooks on top of a guest state the
+/// hypervisor is no longer sure about). If an entry fails, [`rollback_entry`] undoes whatever
+/// `dispatch_client_data` already installed for it before the failure, dispatching on the
+/// entry's own command rather than assuming it was a page hook, so a failed entry doesn't leave a
+/// hook half-installed that later lookups don't know about; a rollback failure is logged but does
+/// not itself fail the batch.
+///
+/// # Arguments
+///
+/// * `vm` - The virtual machine instance the hypercall was issued from.
+/// * `entries_va` - The guest-virtual address of the first `shared::MulticallEntry` in the array.
+/// * `count` - The number of entries in the array, as supplied by the guest in R8.
+/// * `stop_on_error` - Whether to stop the batch at the first failing entry (supplied by the
+///   guest in R9) rather than continuing through the rest.
+///
+/// # Returns
+///
+/// * `Ok(())` if the batch was validated and walked to completion, or stopped early per
+///   `stop_on_error` (individual entries may still have failed; see their `status` fields).
+/// * `Err(HypervisorError::InvalidMulticallCount)` if `count` exceeds `MAX_MULTICALL_ENTRIES`.
+/// * `Err(HypervisorError)` if the array does not lie entirely within readable/writable guest
+///   pages.
+fn dispatch_multicall(vm: &mut Vm, entries_va: u64, count: u64, stop_on_error: bool) -> Result<(), HypervisorError> {
+    if count == 0 {
+        return Ok(());
+    }
+
+    if count > MAX_MULTICALL_ENTRIES {
+        warn!("Multicall batch of {} entries exceeds the maximum of {}", count, MAX_MULTICALL_ENTRIES);
+        return Err(HypervisorError::InvalidMulticallCount);
+    }
+
+    let entry_size = core::mem::size_of::<MulticallEntry>() as u64;
+    let batch_size = entry_size.checked_mul(count).ok_or(HypervisorError::InvalidMulticallCount)?;
+
+    // Validate up front that the whole array lies in readable/writable guest pages before a
+    // single entry is touched, so a bad pointer can't corrupt hooks already installed earlier in
+    // the batch by causing a host fault midway through.
+    PhysicalAddress::validate_guest_range(vm.guest_registers.cr3, entries_va, batch_size)?;
+
+    for i in 0..count {
+        let entry_va = entries_va + i * entry_size;
+        let entry_pa = PhysicalAddress::pa_from_va(vm.guest_registers.cr3, entry_va)?;
+        let entry = MulticallEntry::from_ptr_mut(entry_pa);
+
+        trace!("Multicall entry {}/{}: {:?}", i + 1, count, entry.data);
+
+        // `data` is `MulticallEntry`'s first field, so the entry's guest-virtual address also
+        // points directly at its embedded `ClientData`.
+        match dispatch_client_data(vm, entry_va) {
+            Ok(_) => entry.status = 0,
+            Err(error) => {
+                warn!("Multicall entry {}/{} failed: {:?}", i + 1, count, error);
+
+                if let Err(rollback_error) = rollback_entry(vm, &entry.data) {
+                    warn!("Rollback of multicall entry {}/{} failed: {:?}", i + 1, count, rollback_error);
+                }
+
+                entry.status = 1;
+
+                if stop_on_error {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Serializes the live VMCS state and installed EPT hooks into a guest-provided buffer, for
+/// `VmcallCommand::SnapshotDump`.
+///
+/// # Arguments
+///
+/// * `vm` - The virtual machine instance the hypercall was issued from.
+/// * `buffer_va` - The guest-virtual address of the destination buffer.
+/// * `capacity` - The size, in bytes, of the destination buffer, as supplied by the guest in R8.
+///
+/// # Returns
+///
+/// * `Ok(())` if the snapshot was written successfully.
+/// * `Err(HypervisorError)` if the buffer could not be translated/validated or was too small.
+fn dispatch_snapshot_dump(vm: &mut Vm, buffer_va: u64, capacity: u64) -> Result<(), HypervisorError> {
+    PhysicalAddress::validate_guest_range(vm.guest_registers.cr3, buffer_va, capacity)?;
+
+    let buffer_pa = PhysicalAddress::pa_from_va(vm.guest_registers.cr3, buffer_va)?;
+
+    // The host identity-maps all guest physical memory, so the validated guest-physical range can
+    // be written through directly once translated from the guest-virtual address above.
+    let buffer = unsafe { core::slice::from_raw_parts_mut(buffer_pa as *mut u8, capacity as usize) };
+
+    let written = snapshot::serialize(vm, buffer)?;
+    trace!("Wrote {} byte snapshot to guest buffer at VA {:#x}", written, buffer_va);
+
+    Ok(())
+}
+
+/// Validates and restores a previously dumped snapshot from a guest-provided buffer, for
+/// `VmcallCommand::SnapshotRestore`.
+///
+/// # Arguments
+///
+/// * `vm` - The virtual machine instance the hypercall was issued from.
+/// * `buffer_va` - The guest-virtual address of the buffer holding the snapshot.
+/// * `len` - The size, in bytes, of the snapshot blob, as supplied by the guest in R8.
+///
+/// # Returns
+///
+/// * `Ok(())` if the snapshot's version/size validated and its state was restored.
+/// * `Err(HypervisorError)` if the buffer could not be translated/validated, or the blob failed
+///   its version/size check.
+fn dispatch_snapshot_restore(vm: &mut Vm, buffer_va: u64, len: u64) -> Result<(), HypervisorError> {
+    // Restore only reads the guest's buffer; it must not reject a snapshot blob the guest mapped
+    // read-only, unlike `dispatch_snapshot_dump`/`dispatch_multicall`, which write through theirs.
+    PhysicalAddress::validate_guest_range_readable(vm.guest_registers.cr3, buffer_va, len)?;
+
+    let buffer_pa = PhysicalAddress::pa_from_va(vm.guest_registers.cr3, buffer_va)?;
+
+    // The host identity-maps all guest physical memory, so the validated guest-physical range can
+    // be read through directly once translated from the guest-virtual address above.
+    let buffer = unsafe { core::slice::from_raw_parts(buffer_pa as *const u8, len as usize) };
+
+    snapshot::restore(vm, buffer)
 }
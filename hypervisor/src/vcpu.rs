@@ -0,0 +1,84 @@
+//! Vendor-neutral abstraction over the underlying hardware virtualization extensions (Intel VT-x,
+//! AMD-V), so the rest of the crate can drive a guest without caring which backend is active.
+//!
+//! Following crosvm's approach, everything that talks to hardware virtualization features is
+//! meant to live behind the [`Hypervisor`]/[`Vcpu`] traits defined here. `intel` implements them
+//! on top of VMX/EPT; `amd` implements them on top of SVM/NPT. `driver::virtualize_system` picks
+//! whichever backend matches the CPUID vendor string at boot.
+
+use crate::{error::HypervisorError, intel::capture::GuestRegisters, intel::ept::AccessType};
+
+/// The reason a VM exit occurred, vendor-normalized so callers don't need to branch on the active
+/// backend. Concrete backends translate their own native exit-reason encoding (VMX's
+/// basic-exit-reason field, SVM's `#VMEXIT` code) into this enum.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum VmExitReason {
+    /// The guest executed a hypercall instruction (`VMCALL` on Intel, `VMMCALL` on AMD).
+    Vmcall,
+    /// The guest executed `CPUID`.
+    Cpuid,
+    /// A second-level address-translation violation (EPT violation / `#NPF`).
+    NestedPageFault,
+    /// The monitor-trap/single-step mechanism fired.
+    MonitorTrapFlag,
+    /// An exit reason not yet normalized by this abstraction.
+    Other(u32),
+}
+
+/// A single virtual CPU, abstracted over the backend driving it.
+///
+/// Implementations own everything needed to run one logical guest CPU: its VMCS/VMCB, its EPT/NPT
+/// tables, and the state needed to decode and react to VM exits.
+pub trait Vcpu {
+    /// Enters the guest and runs until the next VM exit.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(VmExitReason)` - The normalized reason execution returned to the hypervisor.
+    /// * `Err(HypervisorError)` - If VM-entry itself failed (e.g. invalid guest state), or the
+    ///   backend failed to handle the resulting exit.
+    fn run(&mut self) -> Result<VmExitReason, HypervisorError>;
+
+    /// Returns a reference to the guest's general-purpose register state as of the last exit.
+    fn guest_registers(&self) -> &GuestRegisters;
+
+    /// Returns a mutable reference to the guest's general-purpose register state, so VM-exit
+    /// handlers can modify what the guest sees on the next VM-entry (e.g. writing a hypercall's
+    /// return value into RAX).
+    fn guest_registers_mut(&mut self) -> &mut GuestRegisters;
+
+    /// Installs or updates a second-level address-translation hook redirecting accesses to
+    /// `guest_pa` to `shadow_pa` for the given access type.
+    ///
+    /// # Arguments
+    ///
+    /// * `guest_pa` - The guest-physical address being hooked.
+    /// * `shadow_pa` - The guest-physical address of the shadow page backing the hook.
+    /// * `access_type` - The access permissions to grant for `guest_pa` after the swap.
+    fn install_hook(&mut self, guest_pa: u64, shadow_pa: u64, access_type: AccessType) -> Result<(), HypervisorError>;
+
+    /// Queues `vector` (with an optional error code) for injection into the guest on a future
+    /// VM-entry.
+    fn inject_event(&mut self, vector: u8, error_code: Option<u32>);
+}
+
+/// The hypervisor itself: the thing that knows how to enable the hardware virtualization
+/// extensions on the current core and hand back a [`Vcpu`] to run.
+pub trait Hypervisor {
+    /// The concrete [`Vcpu`] implementation this backend produces.
+    type Vcpu: Vcpu;
+
+    /// Enables the hardware virtualization extensions on the current core (`VMXON` on Intel,
+    /// setting `EFER.SVME` on AMD) and constructs a [`Vcpu`] ready to be entered.
+    ///
+    /// # Arguments
+    ///
+    /// * `initial_registers` - The guest register state to resume into on first entry.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Self::Vcpu)` - A VCPU ready to run.
+    /// * `Err(HypervisorError)` - If the extensions could not be enabled (unsupported hardware,
+    ///   a locked-off feature-control MSR, allocation failure, ...).
+    fn virtualize_core(initial_registers: &GuestRegisters) -> Result<Self::Vcpu, HypervisorError>;
+}
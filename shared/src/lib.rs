@@ -70,3 +70,33 @@ impl ClientData {
         unsafe { &*(ptr as *const ClientData) }
     }
 }
+
+/// A single entry in a VMCALL multicall batch, pairing one hooking request with the status the
+/// hypervisor writes back once that entry has been processed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct MulticallEntry {
+    /// The hooking operation to perform for this entry.
+    pub data: ClientData,
+
+    /// Status written back by the hypervisor for each entry it processes: `0` on success,
+    /// non-zero on failure. When a batch stops at a failing entry, the entries after it are not
+    /// written and keep the value the guest initialized them with.
+    pub status: i64,
+}
+
+impl MulticallEntry {
+    /// Converts a pointer to a mutable `MulticallEntry`, allowing the hypervisor to write the
+    /// per-entry status back into guest memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `ptr` - The pointer to the `MulticallEntry`.
+    ///
+    /// # Returns
+    ///
+    /// * `&'static mut MulticallEntry` - The mutable reference to the `MulticallEntry`.
+    pub fn from_ptr_mut(ptr: u64) -> &'static mut MulticallEntry {
+        unsafe { &mut *(ptr as *mut MulticallEntry) }
+    }
+}